@@ -1,9 +1,10 @@
 use std::cell::Cell;
+use std::time::Instant;
 
 use comemo::{Track, Tracked, TrackedMut, Validate};
 
 use crate::diag::SourceResult;
-use crate::eval::Tracer;
+use crate::eval::{SpanKind, TraceAction, Tracer};
 use crate::introspection::{Introspector, Locator};
 use crate::syntax::FileId;
 use crate::World;
@@ -43,6 +44,75 @@ impl Engine<'_> {
             }
         }
     }
+
+    /// Enter a profiling span, for use with a tracer that was put into
+    /// profiling mode. The span covers everything that happens until the
+    /// returned guard is dropped, at which point its duration is recorded.
+    /// Observers pushed on the tracer are notified only if its
+    /// [`TraceFilter`] admits `kind` and the current file (see
+    /// `Route::file`); the built-in profiler records the span regardless of
+    /// the filter.
+    ///
+    /// The `label` should identify what is being profiled: a module file
+    /// name, function name, show-rule target, or `"layout"`. This is a thin
+    /// wrapper around the existing `Route::increase`/`decrease` pairing, so
+    /// it also participates in the `MAX_DEPTH` check like any other nesting.
+    ///
+    /// [`TraceFilter`]: crate::eval::TraceFilter
+    pub fn enter_span(&mut self, kind: SpanKind, label: impl Into<String>) -> SpanGuard<'_, 'a> {
+        self.route.increase();
+        let depth = self.route.depth();
+        let file = self.route.file();
+        let label = label.into();
+        self.tracer.span_enter(kind, file, &label, depth);
+        SpanGuard { engine: self, kind, file, label, start: Instant::now(), depth }
+    }
+
+    /// Emit one line of the optional execution trace: an evaluated
+    /// expression, function application, or show-rule invocation. Pay for
+    /// use -- `summary` is only called, and nothing recorded, if a trace
+    /// observer is registered on the tracer and its [`TraceFilter`] admits
+    /// this `file` at trace level; a disabled trace costs one cheap check.
+    ///
+    /// `summary` should build a short (ideally pre-truncated, see
+    /// [`crate::eval::truncate_summary`]) rendering of the relevant value
+    /// or target -- lazily, so hot call sites don't pay for formatting it
+    /// when nothing is listening.
+    ///
+    /// [`TraceFilter`]: crate::eval::TraceFilter
+    pub fn trace(
+        &mut self,
+        action: TraceAction,
+        file: Option<FileId>,
+        summary: impl FnOnce() -> String,
+    ) {
+        if !self.tracer.trace_enabled(action, file) {
+            return;
+        }
+        let depth = self.route.depth();
+        self.tracer.trace(action, file, depth, summary());
+    }
+}
+
+/// A guard for an active profiling span, created by [`Engine::enter_span`].
+/// Notifies the engine's tracer of the span's duration when dropped.
+pub struct SpanGuard<'a, 'b> {
+    engine: &'a mut Engine<'b>,
+    kind: SpanKind,
+    file: Option<FileId>,
+    label: String,
+    start: Instant,
+    depth: usize,
+}
+
+impl Drop for SpanGuard<'_, '_> {
+    fn drop(&mut self) {
+        let dur = self.start.elapsed();
+        self.engine.route.decrease();
+        self.engine
+            .tracer
+            .span_exit(self.kind, self.file, &self.label, self.depth, self.start, dur);
+    }
 }
 
 /// The route the engine took during compilation. This is used to detect
@@ -132,6 +202,36 @@ impl<'a> Route<'a> {
         self.id == Some(id) || self.outer.map_or(false, |outer| outer.contains(id))
     }
 
+    /// The ordered chain of file ids from the first occurrence of `id` on
+    /// the route down to the current segment, so that a diagnostic can
+    /// render the full import cycle (`a.typ -> b.typ -> c.typ`, with the
+    /// caller appending `-> a.typ` for the attempted re-import) instead of
+    /// naming only the offending file. Empty if `id` is not part of the
+    /// route.
+    pub fn cycle_path(&self, id: FileId) -> Vec<FileId> {
+        let mut path = match self.outer {
+            Some(outer) => outer.cycle_path(id),
+            None => Vec::new(),
+        };
+        if self.id == Some(id) || !path.is_empty() {
+            if let Some(segment) = self.id {
+                path.push(segment);
+            }
+        }
+        path
+    }
+
+    /// The total nesting depth at this point in the route: this segment's
+    /// `len` plus the lengths of all `outer` segments.
+    pub fn depth(&self) -> usize {
+        self.len + self.outer.map_or(0, |outer| outer.depth())
+    }
+
+    /// The id of the innermost module evaluation on the route, if any.
+    pub fn file(&self) -> Option<FileId> {
+        self.id.or_else(|| self.outer.and_then(|outer| outer.file()))
+    }
+
     /// Whether the route's depth is less than or equal to the given depth.
     pub fn within(&self, depth: usize) -> bool {
         if self.upper.get().saturating_add(self.len) <= depth {
@@ -157,3 +257,39 @@ impl Default for Route<'_> {
         Self::root()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::VirtualPath;
+
+    fn file(name: &str) -> FileId {
+        FileId::new(None, VirtualPath::new(name))
+    }
+
+    #[test]
+    fn cycle_path_collects_chain_from_first_occurrence() {
+        let a = file("a.typ");
+        let c = file("c.typ");
+
+        let root = Route::root();
+        let first = Route::insert(root.track(), a);
+        // A non-file frame (e.g. a show rule or function call) in between
+        // shouldn't show up in the reconstructed path.
+        let middle = Route::extend(first.track());
+        let last = Route::insert(middle.track(), c);
+
+        assert_eq!(last.cycle_path(a), vec![a, c]);
+    }
+
+    #[test]
+    fn cycle_path_is_empty_when_id_not_on_route() {
+        let a = file("a.typ");
+        let other = file("other.typ");
+
+        let root = Route::root();
+        let first = Route::insert(root.track(), a);
+
+        assert!(first.cycle_path(other).is_empty());
+    }
+}
@@ -0,0 +1,7 @@
+//! Evaluation of source code into module contents.
+
+mod trace_filter;
+mod tracer;
+
+pub use self::trace_filter::{Level, TraceFilter};
+pub use self::tracer::{truncate_summary, Observer, SpanKind, TraceAction, TraceObserver, Tracer};
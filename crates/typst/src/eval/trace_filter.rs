@@ -0,0 +1,188 @@
+use crate::syntax::FileId;
+
+/// How much detail a [`TraceFilter`] directive admits for whatever it
+/// matches, from coarsest to finest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Nothing is recorded.
+    Off,
+    /// Coarse, span-level detail (profiling spans, but not every step).
+    Debug,
+    /// Every step: the full execution trace.
+    Trace,
+}
+
+/// An `env-filter`-style directive string (as in `tracing-subscriber`'s
+/// `EnvFilter`) that selects what the tracer records, e.g.
+/// `show=trace,layout=debug,file:chapter.typ=trace,off`.
+///
+/// Each comma-separated clause is either `target=level` or a bare `level`
+/// that sets the default for anything no other clause names. A target is
+/// `file:<name>` (matching a source file by name) or one of the following
+/// keywords: a span kind (`show`, `layout`, `call`, `module`), the
+/// execution-trace keyword `eval` (an entered/returned expression or call;
+/// `show` doubles as the keyword for a show-rule trace step), or an event
+/// type (`value`, `element`). When checking whether a given kind/file
+/// combination is enabled, the most specific matching directive wins -- a
+/// `file:` target beats a keyword, which beats the bare default -- and
+/// anything unmatched defaults to off.
+///
+/// This only gates events dispatched to *additional* observers pushed with
+/// `Tracer::push_observer` -- span, value, element, and execution-trace
+/// events. It does not affect the built-in value/diagnostic collectors (so
+/// existing `inspect`/`value`/`delay`/`warn` callers are unaffected), nor
+/// the profiler, which records independent of the filter once
+/// `Tracer::start_profiling` has turned it on.
+#[derive(Debug, Clone, Default)]
+pub struct TraceFilter {
+    directives: Vec<Directive>,
+}
+
+#[derive(Debug, Clone)]
+struct Directive {
+    target: Option<Target>,
+    level: Level,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    Kind(String),
+    File(String),
+}
+
+impl TraceFilter {
+    /// An empty filter: nothing is ever enabled.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Parse a directive string. Clauses that cannot be parsed (unknown
+    /// level, empty target) are ignored rather than rejecting the whole
+    /// string.
+    pub fn parse(directives: &str) -> Self {
+        let directives = directives.split(',').filter_map(Directive::parse).collect();
+        Self { directives }
+    }
+
+    /// Whether events of the given kind keyword and (optional) file are
+    /// enabled at `level`, according to the most specific matching
+    /// directive.
+    pub fn enabled(&self, kind: &str, file: Option<FileId>, level: Level) -> bool {
+        self.resolve(kind, file) >= level
+    }
+
+    /// The configured level for the given kind keyword and file, applying
+    /// the most-specific-wins rule. Later directives win ties.
+    fn resolve(&self, kind: &str, file: Option<FileId>) -> Level {
+        let name = file.map(file_name);
+        let mut by_file = None;
+        let mut by_kind = None;
+        let mut default = Level::Off;
+        for directive in &self.directives {
+            match &directive.target {
+                Some(Target::File(want)) if name.as_deref() == Some(want.as_str()) => {
+                    by_file = Some(directive.level);
+                }
+                Some(Target::Kind(want)) if want == kind => {
+                    by_kind = Some(directive.level);
+                }
+                None => default = directive.level,
+                _ => {}
+            }
+        }
+        by_file.or(by_kind).unwrap_or(default)
+    }
+}
+
+impl Directive {
+    fn parse(clause: &str) -> Option<Self> {
+        let clause = clause.trim();
+        if clause.is_empty() {
+            return None;
+        }
+        match clause.split_once('=') {
+            Some((target, level)) => {
+                let level = parse_level(level)?;
+                let target = match target.strip_prefix("file:") {
+                    Some(name) => Target::File(name.to_string()),
+                    None => Target::Kind(target.to_string()),
+                };
+                Some(Self { target: Some(target), level })
+            }
+            None => Some(Self { target: None, level: parse_level(clause)? }),
+        }
+    }
+}
+
+fn parse_level(s: &str) -> Option<Level> {
+    match s.trim() {
+        "off" => Some(Level::Off),
+        "debug" => Some(Level::Debug),
+        "trace" => Some(Level::Trace),
+        _ => None,
+    }
+}
+
+/// The file's name (last path component), used to match `file:<name>`
+/// directive targets.
+fn file_name(id: FileId) -> String {
+    id.vpath()
+        .as_rootless_path()
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::VirtualPath;
+
+    fn file(name: &str) -> FileId {
+        FileId::new(None, VirtualPath::new(name))
+    }
+
+    #[test]
+    fn file_target_is_more_specific_than_kind_target() {
+        let filter = TraceFilter::parse("show=debug,file:chapter.typ=trace");
+        assert!(filter.enabled("show", Some(file("chapter.typ")), Level::Trace));
+        assert!(filter.enabled("show", Some(file("other.typ")), Level::Debug));
+        assert!(!filter.enabled("show", Some(file("other.typ")), Level::Trace));
+    }
+
+    #[test]
+    fn kind_target_is_more_specific_than_bare_default() {
+        let filter = TraceFilter::parse("layout=debug,off");
+        assert!(filter.enabled("layout", None, Level::Debug));
+        assert!(!filter.enabled("layout", None, Level::Trace));
+        assert!(!filter.enabled("call", None, Level::Debug));
+    }
+
+    #[test]
+    fn bare_default_applies_when_nothing_more_specific_matches() {
+        let filter = TraceFilter::parse("show=debug,trace");
+        assert!(filter.enabled("call", None, Level::Trace));
+        assert!(filter.enabled("show", None, Level::Debug));
+        assert!(!filter.enabled("show", None, Level::Trace));
+    }
+
+    #[test]
+    fn later_directive_for_the_same_target_wins() {
+        let filter = TraceFilter::parse("show=trace,show=debug");
+        assert!(filter.enabled("show", None, Level::Debug));
+        assert!(!filter.enabled("show", None, Level::Trace));
+    }
+
+    #[test]
+    fn empty_filter_enables_nothing() {
+        let filter = TraceFilter::none();
+        assert!(!filter.enabled("show", Some(file("chapter.typ")), Level::Debug));
+    }
+
+    #[test]
+    fn unparseable_clauses_are_ignored_rather_than_rejecting_the_whole_string() {
+        let filter = TraceFilter::parse("bogus,show=nonsense,layout=debug");
+        assert!(filter.enabled("layout", None, Level::Debug));
+        assert!(!filter.enabled("show", None, Level::Debug));
+    }
+}
@@ -0,0 +1,575 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use ecow::EcoVec;
+
+use crate::diag::SourceDiagnostic;
+use crate::eval::{Level, TraceFilter};
+use crate::foundations::{Content, Value};
+use crate::syntax::{FileId, Span};
+
+/// An extension point for observing what happens during compilation.
+///
+/// The engine notifies every registered observer of the same events, so a
+/// user can plug in their own (an IDE collecting only values from one file,
+/// a profiler, a step tracer) alongside the built-in ones. All methods have
+/// empty default implementations; implement only the ones you care about.
+pub trait Observer {
+    /// The span whose values are now being inspected (see [`Tracer::inspect`]).
+    fn on_inspect(&mut self, _span: Span) {}
+
+    /// An expression produced a value while its span is being inspected.
+    fn on_value(&mut self, _value: &Value) {}
+
+    /// A fallible operation produced delayed errors (see [`Engine::delayed`]).
+    ///
+    /// [`Engine::delayed`]: crate::engine::Engine::delayed
+    fn on_delayed_error(&mut self, _errors: &EcoVec<SourceDiagnostic>) {}
+
+    /// A non-fatal warning was raised.
+    fn on_warning(&mut self, _warning: &SourceDiagnostic) {}
+
+    /// A profiling span of the given `kind` was entered in `file` (see
+    /// [`Engine::enter_span`]).
+    ///
+    /// [`Engine::enter_span`]: crate::engine::Engine::enter_span
+    fn on_span_enter(
+        &mut self,
+        _kind: SpanKind,
+        _file: Option<FileId>,
+        _label: &str,
+        _depth: usize,
+    ) {
+    }
+
+    /// A profiling span ended, `dur` after it started at `start`.
+    fn on_span_exit(
+        &mut self,
+        _kind: SpanKind,
+        _file: Option<FileId>,
+        _label: &str,
+        _depth: usize,
+        _start: Instant,
+        _dur: Duration,
+    ) {
+    }
+
+    /// An element was produced during realization or layout in `file`.
+    fn on_element(&mut self, _file: Option<FileId>, _element: &Content) {}
+
+    /// One step of the execution trace (see [`Engine::trace`]): an evaluated
+    /// expression, function application, or show-rule invocation.
+    ///
+    /// [`Engine::trace`]: crate::engine::Engine::trace
+    fn on_trace(
+        &mut self,
+        _action: TraceAction,
+        _file: Option<FileId>,
+        _depth: usize,
+        _summary: &str,
+    ) {
+    }
+}
+
+/// What a profiling span represents, used as the target keyword a
+/// [`TraceFilter`] directive matches against (e.g. `layout=debug`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    /// Evaluating a module.
+    Module,
+    /// Calling a function.
+    Call,
+    /// Applying a show rule.
+    Show,
+    /// Performing (nested) layout.
+    Layout,
+}
+
+impl SpanKind {
+    /// The filter keyword for this kind.
+    fn keyword(self) -> &'static str {
+        match self {
+            Self::Module => "module",
+            Self::Call => "call",
+            Self::Show => "show",
+            Self::Layout => "layout",
+        }
+    }
+}
+
+/// An action reported to [`Observer::on_trace`], in the spirit of Boa's VM
+/// `TraceAction`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceAction {
+    /// An expression, function call, or show rule was entered.
+    Enter,
+    /// Execution returned from one, with the produced value summarized.
+    Return,
+    /// A show rule specifically was applied (more specific than `Enter`).
+    ShowRule,
+}
+
+impl TraceAction {
+    /// The filter keyword this action is matched against.
+    fn keyword(self) -> &'static str {
+        match self {
+            Self::ShowRule => "show",
+            Self::Enter | Self::Return => "eval",
+        }
+    }
+}
+
+/// Truncate a value's summary to a reasonable length for a single trace
+/// line, appending an ellipsis if anything was cut off.
+pub fn truncate_summary(s: &str, max_len: usize) -> String {
+    if s.chars().count() <= max_len {
+        return s.to_string();
+    }
+    let mut truncated: String = s.chars().take(max_len).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// An [`Observer`] that records a depth-indented, human-readable line for
+/// every evaluated expression, function application, and show-rule
+/// invocation, in the style of Boa's VM trace mode. Most useful for seeing
+/// what is recursing when compilation aborts with "maximum ... depth
+/// exceeded".
+#[derive(Default)]
+pub struct TraceObserver {
+    lines: Vec<String>,
+}
+
+impl TraceObserver {
+    /// Create a new, empty trace observer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The trace collected so far, one line per step.
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+}
+
+impl Observer for TraceObserver {
+    fn on_trace(
+        &mut self,
+        action: TraceAction,
+        file: Option<FileId>,
+        depth: usize,
+        summary: &str,
+    ) {
+        let verb = match action {
+            TraceAction::Enter => "enter",
+            TraceAction::Return => "return",
+            TraceAction::ShowRule => "show",
+        };
+        let indent = "  ".repeat(depth);
+        let origin = match file {
+            Some(id) => format!("{id:?}"),
+            None => "<unknown>".to_string(),
+        };
+        self.lines.push(format!("{indent}{verb} [{origin}] {summary}"));
+    }
+}
+
+/// Registry of observers notified about what happens during compilation:
+/// which values expressions produce, which errors are delayed or raised as
+/// warnings, and (optionally) profiling spans.
+///
+/// Ships with built-in observers for value inspection and diagnostic
+/// collection, so existing callers of `inspect`/`value`/`delay`/`warn` see
+/// no change in behavior. Push additional [`Observer`]s with
+/// [`Tracer::push_observer`] to react to the same events without touching
+/// this struct.
+#[derive(Default)]
+pub struct Tracer {
+    values: ValueObserver,
+    diagnostics: DiagnosticObserver,
+    profiler: ProfilingObserver,
+    observers: Vec<Box<dyn Observer>>,
+    filter: TraceFilter,
+}
+
+impl Tracer {
+    /// The maximum number of inspected values.
+    pub const MAX_VALUES: usize = 10;
+
+    /// Create a new tracer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an additional observer for the rest of this tracer's
+    /// lifetime. It receives every inspect, delayed-error and warning event;
+    /// span, value, element and trace events reach it only where the
+    /// tracer's [`TraceFilter`] admits them.
+    pub fn push_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    /// Get the inspected values for the span.
+    pub fn values(self) -> Vec<Value> {
+        self.values.values
+    }
+
+    /// Get the delayed errors.
+    pub fn delayed(&mut self) -> EcoVec<SourceDiagnostic> {
+        std::mem::take(&mut self.diagnostics.delayed)
+    }
+
+    /// Get the stored warnings.
+    pub fn warnings(self) -> EcoVec<SourceDiagnostic> {
+        self.diagnostics.warnings
+    }
+
+    /// Turn on span profiling. Until this is called, the built-in profiler
+    /// records nothing from [`Engine::enter_span`]. `enter_span` itself still
+    /// adjusts the route, looks up its depth and file, and reads the clock
+    /// on every call, whether or not profiling is on.
+    ///
+    /// [`Engine::enter_span`]: crate::engine::Engine::enter_span
+    pub fn start_profiling(&mut self) {
+        self.profiler.start();
+    }
+
+    /// Render the recorded spans as a Chrome trace-event JSON document (the
+    /// `{"traceEvents": [...]}` format understood by `chrome://tracing` and
+    /// speedscope).
+    pub fn chrome_trace(&self) -> String {
+        self.profiler.chrome_trace()
+    }
+
+    /// Compute a flat self-time table: for each label, the total time spent
+    /// in spans with that label, excluding time spent in their nested child
+    /// spans. Sorted by descending self time.
+    pub fn self_time_table(&self) -> Vec<(String, Duration)> {
+        self.profiler.self_time_table()
+    }
+
+    /// Set the directive filter that gates which span, trace, and element
+    /// events get dispatched. Unset, the filter is empty and nothing is
+    /// dispatched.
+    pub fn set_filter(&mut self, filter: TraceFilter) {
+        self.filter = filter;
+    }
+}
+
+#[comemo::track]
+impl Tracer {
+    /// Mark a span as the one to inspect.
+    pub fn inspect(&mut self, span: Span) {
+        self.values.on_inspect(span);
+        for observer in &mut self.observers {
+            observer.on_inspect(span);
+        }
+    }
+
+    /// Inspect a value for the span, gated by the [`TraceFilter`] the way
+    /// its doc comment describes.
+    ///
+    /// [`TraceFilter`]: crate::eval::TraceFilter
+    pub fn value(&mut self, value: Value) {
+        self.values.on_value(&value);
+        let file = self.values.span.and_then(Span::id);
+        if self.values.span.is_some()
+            && !self.observers.is_empty()
+            && self.filter.enabled("value", file, Level::Debug)
+        {
+            for observer in &mut self.observers {
+                observer.on_value(&value);
+            }
+        }
+    }
+
+    /// Add a delayed error.
+    pub fn delay(&mut self, errors: EcoVec<SourceDiagnostic>) {
+        self.diagnostics.on_delayed_error(&errors);
+        for observer in &mut self.observers {
+            observer.on_delayed_error(&errors);
+        }
+    }
+
+    /// Add a warning.
+    pub fn warn(&mut self, warning: SourceDiagnostic) {
+        self.diagnostics.on_warning(&warning);
+        for observer in &mut self.observers {
+            observer.on_warning(&warning);
+        }
+    }
+
+    /// Whether span profiling is currently turned on.
+    pub fn is_profiling(&self) -> bool {
+        self.profiler.active
+    }
+
+    /// Notify that a profiling span was entered, gated by the
+    /// [`TraceFilter`] the way its doc comment describes.
+    ///
+    /// [`TraceFilter`]: crate::eval::TraceFilter
+    pub fn span_enter(&mut self, kind: SpanKind, file: Option<FileId>, label: &str, depth: usize) {
+        if self.profiler.active {
+            self.profiler.on_span_enter(kind, file, label, depth);
+        }
+        if !self.observers.is_empty() && self.filter.enabled(kind.keyword(), file, Level::Debug) {
+            for observer in &mut self.observers {
+                observer.on_span_enter(kind, file, label, depth);
+            }
+        }
+    }
+
+    /// Notify that a profiling span ended, with the same built-in-vs-filter
+    /// split as [`Tracer::span_enter`].
+    pub fn span_exit(
+        &mut self,
+        kind: SpanKind,
+        file: Option<FileId>,
+        label: &str,
+        depth: usize,
+        start: Instant,
+        dur: Duration,
+    ) {
+        if self.profiler.active {
+            self.profiler.on_span_exit(kind, file, label, depth, start, dur);
+        }
+        if !self.observers.is_empty() && self.filter.enabled(kind.keyword(), file, Level::Debug) {
+            for observer in &mut self.observers {
+                observer.on_span_exit(kind, file, label, depth, start, dur);
+            }
+        }
+    }
+
+    /// Whether a call to [`Tracer::trace`] for this `action`/`file` would
+    /// actually reach an observer: there is at least one pushed, and the
+    /// filter admits it at trace level. Callers build `summary` eagerly, so
+    /// [`Engine::trace`] checks this first to skip that work entirely when
+    /// nothing is listening.
+    ///
+    /// [`Engine::trace`]: crate::engine::Engine::trace
+    pub fn trace_enabled(&self, action: TraceAction, file: Option<FileId>) -> bool {
+        !self.observers.is_empty() && self.filter.enabled(action.keyword(), file, Level::Trace)
+    }
+
+    /// Dispatch one step of the execution trace to every observer. A no-op
+    /// unless an observer (such as [`TraceObserver`]) was pushed that cares
+    /// -- callers should check [`Tracer::trace_enabled`] first to avoid
+    /// building `summary` for nothing.
+    pub fn trace(&mut self, action: TraceAction, file: Option<FileId>, depth: usize, summary: String) {
+        for observer in &mut self.observers {
+            observer.on_trace(action, file, depth, &summary);
+        }
+    }
+
+    /// Notify observers that an element was produced during realization or
+    /// layout, unless the filter gates it out. The built-in observers don't
+    /// care about elements, so unlike value/diagnostic events this is
+    /// purely for additional observers.
+    pub fn element(&mut self, file: Option<FileId>, element: &Content) {
+        if !self.observers.is_empty() && self.filter.enabled("element", file, Level::Debug) {
+            for observer in &mut self.observers {
+                observer.on_element(file, element);
+            }
+        }
+    }
+}
+
+/// Built-in observer backing [`Tracer::inspect`]/[`Tracer::value`]/
+/// [`Tracer::values`].
+#[derive(Default)]
+struct ValueObserver {
+    span: Option<Span>,
+    values: Vec<Value>,
+}
+
+impl Observer for ValueObserver {
+    fn on_inspect(&mut self, span: Span) {
+        self.span = Some(span);
+    }
+
+    fn on_value(&mut self, value: &Value) {
+        if self.span.is_some() && self.values.len() < Tracer::MAX_VALUES {
+            self.values.push(value.clone());
+        }
+    }
+}
+
+/// Built-in observer backing [`Tracer::delay`]/[`Tracer::warn`] and their
+/// getters.
+#[derive(Default)]
+struct DiagnosticObserver {
+    delayed: EcoVec<SourceDiagnostic>,
+    warnings: EcoVec<SourceDiagnostic>,
+}
+
+impl Observer for DiagnosticObserver {
+    fn on_delayed_error(&mut self, errors: &EcoVec<SourceDiagnostic>) {
+        self.delayed.extend(errors.iter().cloned());
+    }
+
+    fn on_warning(&mut self, warning: &SourceDiagnostic) {
+        if !self.warnings.contains(warning) {
+            self.warnings.push(warning.clone());
+        }
+    }
+}
+
+/// Built-in observer that records profiling spans for
+/// [`Tracer::chrome_trace`] and [`Tracer::self_time_table`].
+struct ProfilingObserver {
+    active: bool,
+    start: Option<Instant>,
+    spans: Vec<SpanEvent>,
+}
+
+impl Default for ProfilingObserver {
+    fn default() -> Self {
+        Self { active: false, start: None, spans: vec![] }
+    }
+}
+
+impl ProfilingObserver {
+    fn start(&mut self) {
+        self.active = true;
+        self.start = Some(Instant::now());
+    }
+
+    fn chrome_trace(&self) -> String {
+        let events: Vec<String> = self
+            .spans
+            .iter()
+            .map(|event| {
+                format!(
+                    "{{\"name\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":0,\"tid\":0}}",
+                    escape_json(&event.label),
+                    event.start_ns / 1_000,
+                    (event.dur_ns / 1_000).max(1),
+                )
+            })
+            .collect();
+        format!("{{\"traceEvents\":[{}]}}", events.join(","))
+    }
+
+    fn self_time_table(&self) -> Vec<(String, Duration)> {
+        // Spans are recorded in completion order, so a span's children
+        // always appear before it. We walk the list once, accumulating
+        // each span's duration into a per-depth bucket that its parent
+        // consumes (and subtracts) when it is later recorded.
+        let mut pending_children: HashMap<usize, u64> = HashMap::new();
+        let mut totals: HashMap<String, u64> = HashMap::new();
+        for event in &self.spans {
+            let children_ns = pending_children.remove(&(event.depth + 1)).unwrap_or(0);
+            let self_ns = event.dur_ns.saturating_sub(children_ns);
+            *totals.entry(event.label.clone()).or_insert(0) += self_ns;
+            *pending_children.entry(event.depth).or_insert(0) += event.dur_ns;
+        }
+
+        let mut table: Vec<_> = totals
+            .into_iter()
+            .map(|(label, ns)| (label, Duration::from_nanos(ns)))
+            .collect();
+        table.sort_by(|a, b| b.1.cmp(&a.1));
+        table
+    }
+}
+
+impl Observer for ProfilingObserver {
+    fn on_span_exit(
+        &mut self,
+        _kind: SpanKind,
+        _file: Option<FileId>,
+        label: &str,
+        depth: usize,
+        start: Instant,
+        dur: Duration,
+    ) {
+        let Some(base) = self.active.then_some(self.start).flatten() else { return };
+        let start_ns = start.saturating_duration_since(base).as_nanos() as u64;
+        let dur_ns = dur.as_nanos() as u64;
+        self.spans.push(SpanEvent { label: label.to_string(), depth, start_ns, dur_ns });
+    }
+}
+
+/// A single completed profiling span, as recorded by [`Engine::enter_span`].
+///
+/// [`Engine::enter_span`]: crate::engine::Engine::enter_span
+#[derive(Debug, Clone)]
+struct SpanEvent {
+    /// A human-readable label: a module file name, function name, show-rule
+    /// target, or `"layout"`.
+    label: String,
+    /// The route depth at which this span was entered (`Route`'s `len` plus
+    /// the lengths of all its `outer` segments).
+    depth: usize,
+    /// Start time, in nanoseconds relative to when profiling began.
+    start_ns: u64,
+    /// How long the span took, in nanoseconds.
+    dur_ns: u64,
+}
+
+/// Escape a string for embedding in a JSON string literal.
+fn escape_json(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(label: &str, depth: usize, start_ns: u64, dur_ns: u64) -> SpanEvent {
+        SpanEvent { label: label.to_string(), depth, start_ns, dur_ns }
+    }
+
+    fn profiler_with(spans: Vec<SpanEvent>) -> ProfilingObserver {
+        ProfilingObserver { active: true, start: Some(Instant::now()), spans }
+    }
+
+    fn self_time_of(table: &[(String, Duration)], label: &str) -> Duration {
+        table.iter().find(|(l, _)| l == label).unwrap().1
+    }
+
+    #[test]
+    fn self_time_subtracts_nested_child_from_parent() {
+        // Recorded in completion order: the child exits before its parent.
+        let profiler = profiler_with(vec![span("child", 1, 10, 40), span("parent", 0, 0, 100)]);
+        let table = profiler.self_time_table();
+        assert_eq!(self_time_of(&table, "child"), Duration::from_nanos(40));
+        assert_eq!(self_time_of(&table, "parent"), Duration::from_nanos(60));
+    }
+
+    #[test]
+    fn self_time_accumulates_multiple_siblings_under_one_parent() {
+        let profiler = profiler_with(vec![
+            span("child", 1, 0, 20),
+            span("child", 1, 20, 30),
+            span("parent", 0, 0, 100),
+        ]);
+        let table = profiler.self_time_table();
+        assert_eq!(self_time_of(&table, "child"), Duration::from_nanos(50));
+        assert_eq!(self_time_of(&table, "parent"), Duration::from_nanos(50));
+    }
+
+    #[test]
+    fn chrome_trace_renders_one_event_per_span() {
+        let profiler = profiler_with(vec![span("parse", 0, 2_500, 500)]);
+        assert_eq!(
+            profiler.chrome_trace(),
+            "{\"traceEvents\":[{\"name\":\"parse\",\"ph\":\"X\",\"ts\":2,\"dur\":1,\"pid\":0,\"tid\":0}]}"
+        );
+    }
+
+    #[test]
+    fn escape_json_escapes_quotes_backslashes_and_newlines() {
+        assert_eq!(escape_json("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}